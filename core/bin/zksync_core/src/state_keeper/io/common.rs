@@ -1,4 +1,8 @@
-use std::time::Duration;
+use std::{
+    fmt,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use vm::{
     vm_with_bootloader::{BlockContext, BlockContextMode},
@@ -12,16 +16,127 @@ use zksync_utils::h256_to_u256;
 use super::{L1BatchParams, PendingBatchData};
 use crate::state_keeper::extractors;
 
-/// Returns the parameters required to initialize the VM for the next L1 batch.
+/// Clamps a requested L1 batch timestamp so that it strictly advances the previous batch's
+/// timestamp. The VM and the downstream proof system assume strictly increasing block
+/// timestamps, so this guards against clock skew or a replayed pending miniblock producing
+/// a non-increasing timestamp that the bootloader would reject.
+fn next_l1_batch_timestamp(prev_timestamp: u64, requested: u64) -> u64 {
+    requested.max(prev_timestamp + 1)
+}
+
+/// Source of the timestamp to use for the next L1 batch.
+pub(crate) trait BatchTimestampProvider: fmt::Debug + Send + Sync {
+    /// Returns the timestamp for the batch following one sealed at `prev_timestamp`.
+    fn next_batch_timestamp(&self, prev_timestamp: u64) -> u64;
+}
+
+/// Production implementation: advances batch time to the current wall-clock time.
+#[derive(Debug, Default)]
+pub(crate) struct SystemTimeBatchTimestampProvider;
+
+impl BatchTimestampProvider for SystemTimeBatchTimestampProvider {
+    fn next_batch_timestamp(&self, _prev_timestamp: u64) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("incorrect system clock")
+            .as_secs()
+    }
+}
+
+/// Replays a timestamp that was already recorded (e.g. a pending miniblock's) instead of
+/// deriving a new one.
+#[derive(Debug)]
+struct FixedBatchTimestampProvider(u64);
+
+impl BatchTimestampProvider for FixedBatchTimestampProvider {
+    fn next_batch_timestamp(&self, _prev_timestamp: u64) -> u64 {
+        self.0
+    }
+}
+
+/// Holds a one-shot next-batch timestamp override and an optional persistent per-batch
+/// interval. They take effect only through an `OverridableBatchTimestampProvider` that wraps
+/// this struct; nothing else in this module reads or sets them. Must not be applied when
+/// resuming a pending batch, since that replays an already-persisted timestamp.
+#[derive(Debug, Default)]
+pub(crate) struct BatchTimestampOverrides {
+    next_timestamp: Mutex<Option<u64>>,
+    interval: Mutex<Option<u64>>,
+}
+
+impl BatchTimestampOverrides {
+    /// Forces the very next batch timestamp to be `timestamp`; consumed exactly once.
+    pub(crate) fn set_next_timestamp(&self, timestamp: u64) {
+        *self.next_timestamp.lock().unwrap() = Some(timestamp);
+    }
+
+    /// Makes every subsequent batch's timestamp equal to `previous + interval`, until cleared.
+    pub(crate) fn set_interval(&self, interval: Option<u64>) {
+        *self.interval.lock().unwrap() = interval;
+    }
+
+    /// Resolves the timestamp to request for the next batch: the one-shot override (if set),
+    /// else the persistent interval applied to `prev_timestamp` (if set), else `fallback()`.
+    fn resolve(&self, prev_timestamp: u64, fallback: impl FnOnce() -> u64) -> u64 {
+        if let Some(timestamp) = self.next_timestamp.lock().unwrap().take() {
+            return timestamp;
+        }
+        match *self.interval.lock().unwrap() {
+            Some(interval) => prev_timestamp + interval,
+            None => fallback(),
+        }
+    }
+}
+
+/// Wraps a `BatchTimestampProvider` so its result can be overridden via `BatchTimestampOverrides`.
+#[derive(Debug)]
+pub(crate) struct OverridableBatchTimestampProvider<'a, P = SystemTimeBatchTimestampProvider> {
+    inner: P,
+    overrides: &'a BatchTimestampOverrides,
+}
+
+impl<'a, P: BatchTimestampProvider> OverridableBatchTimestampProvider<'a, P> {
+    pub(crate) fn new(inner: P, overrides: &'a BatchTimestampOverrides) -> Self {
+        Self { inner, overrides }
+    }
+}
+
+impl<P: BatchTimestampProvider> BatchTimestampProvider for OverridableBatchTimestampProvider<'_, P> {
+    fn next_batch_timestamp(&self, prev_timestamp: u64) -> u64 {
+        self.overrides
+            .resolve(prev_timestamp, || self.inner.next_batch_timestamp(prev_timestamp))
+    }
+}
+
+/// Test-only implementation that steps the previous batch's timestamp by a fixed amount.
+#[cfg(test)]
+#[derive(Debug)]
+pub(crate) struct IncrementingBatchTimestampProvider {
+    pub increment: u64,
+}
+
+#[cfg(test)]
+impl BatchTimestampProvider for IncrementingBatchTimestampProvider {
+    fn next_batch_timestamp(&self, prev_timestamp: u64) -> u64 {
+        prev_timestamp + self.increment
+    }
+}
+
+/// Returns the parameters required to initialize the VM for the next L1 batch, along with
+/// the (possibly clamped, see `next_l1_batch_timestamp`) timestamp that was actually used.
 pub(crate) fn l1_batch_params(
     current_l1_batch_number: L1BatchNumber,
     operator_address: Address,
-    l1_batch_timestamp: u64,
+    timestamp_provider: &dyn BatchTimestampProvider,
+    previous_batch_timestamp: u64,
     previous_block_hash: U256,
     l1_gas_price: u64,
     fair_l2_gas_price: u64,
     base_system_contracts: BaseSystemContracts,
-) -> L1BatchParams {
+) -> (L1BatchParams, u64) {
+    let requested_timestamp = timestamp_provider.next_batch_timestamp(previous_batch_timestamp);
+    let l1_batch_timestamp = next_l1_batch_timestamp(previous_batch_timestamp, requested_timestamp);
+
     let block_properties = BlockProperties {
         default_aa_code_hash: h256_to_u256(base_system_contracts.default_aa.hash),
         zkporter_is_available: ZKPORTER_IS_AVAILABLE,
@@ -35,11 +150,12 @@ pub(crate) fn l1_batch_params(
         operator_address,
     };
 
-    L1BatchParams {
+    let params = L1BatchParams {
         context_mode: BlockContextMode::NewBlock(context.into(), previous_block_hash),
         properties: block_properties,
         base_system_contracts,
-    }
+    };
+    (params, l1_batch_timestamp)
 }
 
 /// Returns the amount of iterations `delay_interval` fits into `max_wait`, rounding up.
@@ -73,7 +189,7 @@ pub(crate) async fn load_pending_batch(
         .await?;
 
     vlog::info!("Getting previous batch hash");
-    let (previous_l1_batch_hash, _) =
+    let (previous_l1_batch_hash, previous_l1_batch_timestamp) =
         extractors::wait_for_prev_l1_batch_params(storage, current_l1_batch_number).await;
 
     let base_system_contracts = storage
@@ -89,15 +205,33 @@ pub(crate) async fn load_pending_batch(
         .await;
 
     vlog::info!("Previous l1_batch_hash: {}", previous_l1_batch_hash);
-    let params = l1_batch_params(
+    // NB: pending transactions were already executed and persisted under
+    // `pending_miniblock_header.timestamp`, so this must replay that exact value rather than
+    // going through `BatchTimestampOverrides` — an override meant for the next *new* batch
+    // must never silently change the timestamp used to re-execute already-committed ones.
+    let timestamp_provider = FixedBatchTimestampProvider(pending_miniblock_header.timestamp);
+    let (params, l1_batch_timestamp) = l1_batch_params(
         current_l1_batch_number,
         fee_account,
-        pending_miniblock_header.timestamp,
+        &timestamp_provider,
+        previous_l1_batch_timestamp,
         previous_l1_batch_hash,
         pending_miniblock_header.l1_gas_price,
         pending_miniblock_header.l2_fair_gas_price,
         base_system_contracts,
     );
+    // The miniblocks/transactions queued for re-execution below were already executed and
+    // persisted under `pending_miniblock_header.timestamp`. If that timestamp doesn't already
+    // satisfy monotonicity, the invariant was violated before this code ever ran (e.g. a clock
+    // step back across a restart); refuse to resume rather than re-execute already-committed
+    // transactions under a different `block_timestamp`, which could diverge from the state
+    // already on disk.
+    assert_eq!(
+        l1_batch_timestamp, pending_miniblock_header.timestamp,
+        "pending L1 batch timestamp {} is not strictly greater than the previous batch's \
+         timestamp {}; refusing to resume",
+        pending_miniblock_header.timestamp, previous_l1_batch_timestamp
+    );
 
     let txs = storage
         .transactions_dal()
@@ -120,4 +254,78 @@ mod tests {
         assert_eq!(poll_iters(Duration::from_millis(100), Duration::from_millis(200)), 2);
         assert_eq!(poll_iters(Duration::from_millis(100), Duration::from_millis(201)), 3);
     }
+
+    #[test]
+    fn test_next_l1_batch_timestamp() {
+        // A requested timestamp that already advances time is used as-is.
+        assert_eq!(next_l1_batch_timestamp(100, 101), 101);
+        // A requested timestamp that doesn't advance time gets clamped.
+        assert_eq!(next_l1_batch_timestamp(100, 100), 101);
+        assert_eq!(next_l1_batch_timestamp(100, 50), 101);
+    }
+
+    #[test]
+    fn test_incrementing_batch_timestamp_provider() {
+        let provider = IncrementingBatchTimestampProvider { increment: 5 };
+        assert_eq!(provider.next_batch_timestamp(100), 105);
+        assert_eq!(provider.next_batch_timestamp(105), 110);
+    }
+
+    #[test]
+    fn test_fixed_batch_timestamp_provider() {
+        let provider = FixedBatchTimestampProvider(42);
+        assert_eq!(provider.next_batch_timestamp(0), 42);
+        assert_eq!(provider.next_batch_timestamp(100), 42);
+    }
+
+    #[test]
+    fn test_overridable_batch_timestamp_provider_falls_back_to_inner() {
+        let overrides = BatchTimestampOverrides::default();
+        let provider = OverridableBatchTimestampProvider::new(
+            IncrementingBatchTimestampProvider { increment: 5 },
+            &overrides,
+        );
+        assert_eq!(provider.next_batch_timestamp(100), 105);
+    }
+
+    #[test]
+    fn test_overridable_batch_timestamp_provider_one_shot_override() {
+        let overrides = BatchTimestampOverrides::default();
+        let provider = OverridableBatchTimestampProvider::new(
+            IncrementingBatchTimestampProvider { increment: 5 },
+            &overrides,
+        );
+        overrides.set_next_timestamp(1000);
+        assert_eq!(provider.next_batch_timestamp(100), 1000);
+        // The override is consumed after one use.
+        assert_eq!(provider.next_batch_timestamp(1000), 1005);
+    }
+
+    #[test]
+    fn test_overridable_batch_timestamp_provider_interval_override() {
+        let overrides = BatchTimestampOverrides::default();
+        let provider = OverridableBatchTimestampProvider::new(
+            IncrementingBatchTimestampProvider { increment: 5 },
+            &overrides,
+        );
+        overrides.set_interval(Some(10));
+        assert_eq!(provider.next_batch_timestamp(100), 110);
+        assert_eq!(provider.next_batch_timestamp(110), 120);
+
+        overrides.set_interval(None);
+        assert_eq!(provider.next_batch_timestamp(120), 125);
+    }
+
+    #[test]
+    fn test_overridable_batch_timestamp_provider_one_shot_takes_precedence() {
+        let overrides = BatchTimestampOverrides::default();
+        let provider = OverridableBatchTimestampProvider::new(
+            IncrementingBatchTimestampProvider { increment: 5 },
+            &overrides,
+        );
+        overrides.set_interval(Some(10));
+        overrides.set_next_timestamp(1000);
+        assert_eq!(provider.next_batch_timestamp(100), 1000);
+        assert_eq!(provider.next_batch_timestamp(1000), 1010);
+    }
 }